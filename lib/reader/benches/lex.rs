@@ -0,0 +1,57 @@
+// Benchmark for the `.cton` lexer's byte-cursor dispatch.
+//
+// Run with a release build: `cargo bench --bench lex`. The input is a synthetic function body
+// repeated many times over, which is representative of the token mix (identifiers, numbers,
+// punctuation, comments) a real `.cton` file produces, without requiring a checked-in multi-MB
+// fixture.
+
+#![feature(test)]
+
+extern crate test;
+extern crate cretonne_reader;
+
+use test::Bencher;
+use cretonne_reader::lexer::Lexer;
+
+// One function body's worth of representative `.cton` source.
+const UNIT: &str = "function %foo(i32, f64) -> i32 {
+    ss0 = stack_slot 8
+    jt0 = jump_table [ebb1, ebb2]
+
+ebb0(v0: i32, v1: f64):
+    v2 = iconst.i32 0x1_2345
+    v3 = f64const 0x0.4p-34
+    v4 = iadd v0, v2
+    brnz v4, ebb1
+    jump ebb2
+
+ebb1:
+    ; Fall through to a bigger constant.
+    v5 = iconst.i32 -1
+    return v5
+
+ebb2:
+    return v2
+}
+
+";
+
+fn big_source(repetitions: usize) -> String {
+    let mut s = String::with_capacity(UNIT.len() * repetitions);
+    for _ in 0..repetitions {
+        s.push_str(UNIT);
+    }
+    s
+}
+
+#[bench]
+fn lex_large_file(b: &mut Bencher) {
+    let source = big_source(500);
+    b.bytes = source.len() as u64;
+    b.iter(|| {
+        let mut lex = Lexer::new(&source);
+        while let Some(res) = lex.next() {
+            test::black_box(res);
+        }
+    });
+}
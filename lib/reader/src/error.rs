@@ -0,0 +1,105 @@
+//! Source location tracking for the .cton text format reader.
+
+/// A position in the source text.
+///
+/// A `Location` combines a 1-based line number with a 1-based column number and the byte offset
+/// from the start of the source. The line/column pair is what gets printed in diagnostics; the
+/// byte offset lets callers slice back into the original source, for example to recover the
+/// exact span of a token for an under-squiggle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line_number: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+/// The text of a source line, plus the column range within it to underline for a diagnostic.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SourceContext<'a> {
+    /// The full text of the line containing the span, not including the trailing `'\n'`.
+    pub line: &'a str,
+    /// Column of the first byte to underline, 1-based.
+    pub start_column: usize,
+    /// Column one past the last byte to underline, 1-based. If the span doesn't end on this
+    /// line, this is one past the last column of the line.
+    pub end_column: usize,
+}
+
+/// Look up the source line containing `start`, and compute the column range that a `(start,
+/// end)` span should underline within it, so a caller can print
+///
+/// ```text
+/// <line>
+///      ^^^^
+/// ```
+///
+/// Handles `start` at end-of-source (past the last line, which may have no trailing `'\n'`) by
+/// returning the trailing partial line.
+pub fn source_context<'a>(source: &'a str, start: Location, end: Location) -> SourceContext<'a> {
+    let line_begin = source[..start.offset].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = source[start.offset..]
+        .find('\n')
+        .map_or(source.len(), |idx| start.offset + idx);
+    let line = &source[line_begin..line_end];
+    let end_column = if end.line_number == start.line_number {
+        end.column
+    } else {
+        // The span continues past this line; underline to the end of it.
+        line.len() + 1
+    };
+    SourceContext {
+        line: line,
+        start_column: start.column,
+        end_column: end_column,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loc(line_number: usize, column: usize, offset: usize) -> Location {
+        Location {
+            line_number: line_number,
+            column: column,
+            offset: offset,
+        }
+    }
+
+    #[test]
+    fn single_line() {
+        let source = "function %foo() {}\n";
+        let ctx = source_context(source, loc(1, 10, 9), loc(1, 13, 12));
+        assert_eq!(ctx.line, "function %foo() {}");
+        assert_eq!(ctx.start_column, 10);
+        assert_eq!(ctx.end_column, 13);
+    }
+
+    #[test]
+    fn second_line() {
+        let source = "function %foo() {\nebb0:\n}\n";
+        let ctx = source_context(source, loc(2, 1, 18), loc(2, 5, 22));
+        assert_eq!(ctx.line, "ebb0:");
+        assert_eq!(ctx.start_column, 1);
+        assert_eq!(ctx.end_column, 5);
+    }
+
+    #[test]
+    fn last_line_without_trailing_newline() {
+        let source = "function %foo() {\n}";
+        let ctx = source_context(source, loc(2, 1, 18), loc(2, 2, 19));
+        assert_eq!(ctx.line, "}");
+        assert_eq!(ctx.start_column, 1);
+        assert_eq!(ctx.end_column, 2);
+    }
+
+    #[test]
+    fn error_at_end_of_source() {
+        let source = "function %foo(";
+        let eof = loc(1, 15, 14);
+        let ctx = source_context(source, eof, eof);
+        assert_eq!(ctx.line, "function %foo(");
+        assert_eq!(ctx.start_column, 15);
+        assert_eq!(ctx.end_column, 15);
+    }
+}
@@ -5,7 +5,7 @@
 //
 // ====--------------------------------------------------------------------------------------====//
 
-use std::str::CharIndices;
+use std::borrow::Cow;
 use std::u16;
 use cretonne::ir::types;
 use cretonne::ir::{Value, Ebb};
@@ -15,7 +15,7 @@ use error::Location;
 ///
 /// Some variants may contains references to the original source text, so the `Token` has the same
 /// lifetime as the source.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Token<'a> {
     Comment(&'a str),
     LPar, // '('
@@ -29,6 +29,9 @@ pub enum Token<'a> {
     Arrow, // '->'
     Float(&'a str), // Floating point immediate
     Integer(&'a str), // Integer immediate
+    // A quoted string literal. Borrowed from the source when it contains no escapes, owned
+    // otherwise.
+    String(Cow<'a, str>),
     Type(types::Type), // i32, f32, b32x4, ...
     Value(Value), // v12, vx7
     Ebb(Ebb), // ebb3
@@ -39,18 +42,24 @@ pub enum Token<'a> {
     Identifier(&'a str), // Unrecognized identifier (opcode, enumerator, ...)
 }
 
-/// A `Token` with an associated location.
+/// A `Token` with an associated span, given as the `Location` of its first and one-past-its-last
+/// byte.
 #[derive(Debug, PartialEq, Eq)]
 pub struct LocatedToken<'a> {
     pub token: Token<'a>,
-    pub location: Location,
+    pub start: Location,
+    pub end: Location,
 }
 
-/// Wrap up a `Token` with the given location.
-fn token<'a>(token: Token<'a>, loc: Location) -> Result<LocatedToken<'a>, LocatedError> {
+/// Wrap up a `Token` with the given span.
+fn token<'a>(token: Token<'a>,
+              start: Location,
+              end: Location)
+              -> Result<LocatedToken<'a>, LocatedError> {
     Ok(LocatedToken {
         token: token,
-        location: loc,
+        start: start,
+        end: end,
     })
 }
 
@@ -58,20 +67,40 @@ fn token<'a>(token: Token<'a>, loc: Location) -> Result<LocatedToken<'a>, Locate
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     InvalidChar,
+    /// A `0x` prefix was not followed by at least one hexadecimal digit.
+    ExpectedHexadecimalDigit,
+    /// An `e`/`E`/`p`/`P` exponent marker was not followed by an optional sign and at least one
+    /// digit.
+    ExpectedFloatExponent,
+    /// A `NaN:`/`sNaN:` prefix was not followed by a hexadecimal payload.
+    ExpectedNaNPayload,
+    /// The characters making up a number don't form any recognized number format.
+    InvalidNumberFormat,
+    /// End-of-source was reached before a string literal's closing quote.
+    UnterminatedString,
+    /// A raw control character appeared inside a string literal.
+    ControlCharInString,
+    /// A `\` in a string literal was not followed by a recognized escape.
+    InvalidStringEscape,
+    /// End-of-source was reached before a `#|` block comment's matching `|#`.
+    UnterminatedBlockComment,
 }
 
-/// An `Error` with an associated Location.
+/// An `Error` with an associated span, given as the `Location` of its first and one-past-its-last
+/// byte.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LocatedError {
     pub error: Error,
-    pub location: Location,
+    pub start: Location,
+    pub end: Location,
 }
 
-/// Wrap up an `Error` with the given location.
-fn error<'a>(error: Error, loc: Location) -> Result<LocatedToken<'a>, LocatedError> {
+/// Wrap up an `Error` with the given span.
+fn error<'a>(error: Error, start: Location, end: Location) -> Result<LocatedToken<'a>, LocatedError> {
     Err(LocatedError {
         error: error,
-        location: loc,
+        start: start,
+        end: end,
     })
 }
 
@@ -81,6 +110,34 @@ fn trailing_digits(s: &str) -> usize {
     s.as_bytes().iter().rev().cloned().take_while(|&b| b'0' <= b && b <= b'9').count()
 }
 
+/// Decode the backslash escapes in the raw text of a string literal.
+///
+/// The caller (`Lexer::scan_string`) has already verified that every escape in `raw` is one of
+/// `\n`, `\t`, `\\`, `\"`, or `\xNN`, so this never has to handle a malformed escape.
+fn unescape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some('"') => out.push('"'),
+            Some('x') => {
+                let hi = chars.next().and_then(|c| c.to_digit(16)).unwrap();
+                let lo = chars.next().and_then(|c| c.to_digit(16)).unwrap();
+                out.push(((hi * 16 + lo) as u8) as char);
+            }
+            _ => unreachable!("scan_string should have rejected this escape"),
+        }
+    }
+    out
+}
+
 /// Pre-parse a supposed entity name by splitting it into two parts: A head of lowercase ASCII
 /// letters and numeric tail.
 pub fn split_entity_name(name: &str) -> Option<(&str, u32)> {
@@ -92,66 +149,120 @@ pub fn split_entity_name(name: &str) -> Option<(&str, u32)> {
     }
 }
 
+/// The lexical class of a single source byte.
+///
+/// `Lexer::next` dispatches on this instead of decoding a `char` for every token, since almost
+/// every cton token is pure ASCII. Only `scan_word` and `scan_block_comment` ever fall back to
+/// decoding a `char`, for the rare identifier or block comment that contains non-ASCII text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteClass {
+    Whitespace,
+    Comment, // `;`
+    Hash, // `#`, which starts a `#|...|#` block comment, or is otherwise invalid
+    Quote, // `"`
+    Punctuation, // one of `(` `)` `{` `}` `,` `.` `:` `=`
+    Sign, // `-`, which starts either `->` or a number
+    Digit,
+    IdentStart, // ASCII letter or `_`
+    Other,
+}
+
+/// Build the 256-entry byte-to-class lookup table used by `Lexer::next`.
+fn build_class_table() -> [ByteClass; 256] {
+    let mut table = [ByteClass::Other; 256];
+    for (i, class) in table.iter_mut().enumerate() {
+        *class = match i as u8 {
+            b' ' | b'\t' | b'\r' | b'\n' => ByteClass::Whitespace,
+            b';' => ByteClass::Comment,
+            b'#' => ByteClass::Hash,
+            b'"' => ByteClass::Quote,
+            b'(' | b')' | b'{' | b'}' | b',' | b'.' | b':' | b'=' => ByteClass::Punctuation,
+            b'-' => ByteClass::Sign,
+            b'0'..=b'9' => ByteClass::Digit,
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => ByteClass::IdentStart,
+            _ => ByteClass::Other,
+        };
+    }
+    table
+}
+
 /// Lexical analysis.
 ///
 /// A `Lexer` reads text from a `&str` and provides a sequence of tokens.
 ///
-/// Also keep track of a line number for error reporting.
+/// Also keep track of a line number, column number, and byte offset for error reporting.
 ///
+/// Internally, the lexer walks `source` a byte at a time rather than decoding `char`s, since the
+/// source is overwhelmingly ASCII. Bytes are always valid UTF-8 boundaries when a token's text is
+/// sliced out, because scanning only ever stops at an ASCII byte, at the first byte of a
+/// non-ASCII `char`, or at the end of the source.
 pub struct Lexer<'a> {
     // Complete source being processed.
     source: &'a str,
 
-    // Iterator into `source`.
-    chars: CharIndices<'a>,
+    // Next byte to be processed, or `None` at the end.
+    lookahead: Option<u8>,
 
-    // Next character to be processed, or `None` at the end.
-    lookahead: Option<char>,
-
-    // Index into `source` of lookahead character.
+    // Index into `source` of the lookahead byte.
     pos: usize,
 
     // Current line number.
     line_number: usize,
+
+    // Current column number of `lookahead`, reset to 1 at the start of each line.
+    column: usize,
+
+    // Byte-to-`ByteClass` dispatch table, built once per lexer.
+    class_table: [ByteClass; 256],
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(s: &'a str) -> Lexer {
         let mut lex = Lexer {
             source: s,
-            chars: s.char_indices(),
             lookahead: None,
             pos: 0,
             line_number: 1,
+            column: 0,
+            class_table: build_class_table(),
         };
-        // Advance to the first char.
+        // Advance to the first byte.
         lex.next_ch();
         lex
     }
 
-    // Advance to the next character.
-    // Return the next lookahead character, or None when the end is encountered.
-    // Always update cur_ch to reflect
-    fn next_ch(&mut self) -> Option<char> {
-        if self.lookahead == Some('\n') {
+    // Advance to the next byte.
+    // Return the next lookahead byte, or None when the end is encountered.
+    fn next_ch(&mut self) -> Option<u8> {
+        if self.lookahead == Some(b'\n') {
             self.line_number += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
         }
-        match self.chars.next() {
-            Some((idx, ch)) => {
-                self.pos = idx;
-                self.lookahead = Some(ch);
-            }
-            None => {
-                self.pos = self.source.len();
-                self.lookahead = None;
-            }
+        if self.lookahead.is_some() {
+            self.pos += 1;
         }
+        self.lookahead = self.source.as_bytes().get(self.pos).cloned();
         self.lookahead
     }
 
+    // Having just seen a non-ASCII lead byte in `lookahead`, advance past `ch`, the `char` the
+    // caller decoded starting there, in one step, so `pos` never stops in the middle of a
+    // multi-byte UTF-8 sequence.
+    fn advance_by_char(&mut self, ch: char) {
+        self.column += 1;
+        self.pos += ch.len_utf8();
+        self.lookahead = self.source.as_bytes().get(self.pos).cloned();
+    }
+
     // Get the location corresponding to `lookahead`.
     fn loc(&self) -> Location {
-        Location { line_number: self.line_number }
+        Location {
+            line_number: self.line_number,
+            column: self.column,
+            offset: self.pos,
+        }
     }
 
     // Starting from `lookahead`, are we looking at `prefix`?
@@ -162,9 +273,10 @@ impl<'a> Lexer<'a> {
     // Scan a single-char token.
     fn scan_char(&mut self, tok: Token<'a>) -> Result<LocatedToken<'a>, LocatedError> {
         assert!(self.lookahead != None);
-        let loc = self.loc();
+        let start = self.loc();
         self.next_ch();
-        token(tok, loc)
+        let end = self.loc();
+        token(tok, start, end)
     }
 
     // Scan a multi-char token.
@@ -172,12 +284,13 @@ impl<'a> Lexer<'a> {
                   count: usize,
                   tok: Token<'a>)
                   -> Result<LocatedToken<'a>, LocatedError> {
-        let loc = self.loc();
+        let start = self.loc();
         for _ in 0..count {
             assert!(self.lookahead != None);
             self.next_ch();
         }
-        token(tok, loc)
+        let end = self.loc();
+        token(tok, start, end)
     }
 
     /// Get the rest of the current line.
@@ -186,7 +299,7 @@ impl<'a> Lexer<'a> {
         let begin = self.pos;
         loop {
             match self.next_ch() {
-                None | Some('\n') => return &self.source[begin..self.pos],
+                None | Some(b'\n') => return &self.source[begin..self.pos],
                 _ => {}
             }
         }
@@ -194,9 +307,120 @@ impl<'a> Lexer<'a> {
 
     // Scan a comment extending to the end of the current line.
     fn scan_comment(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
-        let loc = self.loc();
+        let start = self.loc();
         let text = self.rest_of_line();
-        return token(Token::Comment(text), loc);
+        let end = self.loc();
+        token(Token::Comment(text), start, end)
+    }
+
+    // Scan a nested block comment delimited by `#| ... |#`, starting at the opening `#`.
+    //
+    // Nesting depth increments on each `#|` and decrements on each `|#`; the resulting
+    // `Token::Comment` spans from the outermost opener to its matching closer, with any embedded
+    // newlines advancing `line_number` along the way. Returns `UnterminatedBlockComment` (carrying
+    // the opening `Location`) if the source ends before the depth returns to zero.
+    fn scan_block_comment(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
+        let begin = self.pos;
+        let start = self.loc();
+        let mut depth = 0usize;
+
+        loop {
+            if self.looking_at("#|") {
+                depth += 1;
+                self.next_ch();
+                self.next_ch();
+            } else if self.looking_at("|#") {
+                depth -= 1;
+                self.next_ch();
+                self.next_ch();
+                if depth == 0 {
+                    break;
+                }
+            } else if self.lookahead.is_none() {
+                return error(Error::UnterminatedBlockComment, start, self.loc());
+            } else if self.lookahead.unwrap() >= 0x80 {
+                // Decode the full `char` in one step so `pos` never stops in the middle of a
+                // multi-byte UTF-8 sequence, which would make the next `looking_at` call panic.
+                let ch = self.source[self.pos..].chars().next().unwrap();
+                self.advance_by_char(ch);
+            } else {
+                self.next_ch();
+            }
+        }
+
+        let text = &self.source[begin..self.pos];
+        let end = self.loc();
+        token(Token::Comment(text), start, end)
+    }
+
+    // Scan a quoted string literal, starting at the opening `"`.
+    //
+    // Accepts the escapes `\n`, `\t`, `\\`, `\"`, and `\xNN` (a hexadecimal byte value). Returns
+    // `UnterminatedString` if the source ends before the closing quote, `ControlCharInString` for
+    // a raw control character in the literal, and `InvalidStringEscape` for anything following a
+    // `\` that isn't one of the escapes above.
+    fn scan_string(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
+        let start = self.loc();
+        self.next_ch(); // Skip the opening quote.
+        let text_begin = self.pos;
+        let mut escaped = false;
+
+        loop {
+            match self.lookahead {
+                None => {
+                    let end = self.loc();
+                    return error(Error::UnterminatedString, start, end);
+                }
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    escaped = true;
+                    match self.next_ch() {
+                        None => {
+                            let end = self.loc();
+                            return error(Error::UnterminatedString, start, end);
+                        }
+                        Some(b'n') | Some(b't') | Some(b'\\') | Some(b'"') => {
+                            self.next_ch();
+                        }
+                        Some(b'x') => {
+                            self.next_ch();
+                            for _ in 0..2 {
+                                match self.lookahead {
+                                    Some(ch) if ch.is_ascii_hexdigit() => {
+                                        self.next_ch();
+                                    }
+                                    _ => {
+                                        let end = self.loc();
+                                        return error(Error::InvalidStringEscape, start, end);
+                                    }
+                                }
+                            }
+                        }
+                        Some(_) => {
+                            let end = self.loc();
+                            return error(Error::InvalidStringEscape, start, end);
+                        }
+                    }
+                }
+                Some(ch) if ch < 0x20 => {
+                    let end = self.loc();
+                    return error(Error::ControlCharInString, start, end);
+                }
+                Some(_) => {
+                    self.next_ch();
+                }
+            }
+        }
+
+        let raw = &self.source[text_begin..self.pos];
+        self.next_ch(); // Skip the closing quote.
+        let end = self.loc();
+        let text = if escaped {
+            Cow::Owned(unescape(raw))
+        } else {
+            Cow::Borrowed(raw)
+        };
+        token(Token::String(text), start, end)
     }
 
     // Scan a number token which can represent either an integer or floating point number.
@@ -213,60 +437,143 @@ impl<'a> Lexer<'a> {
     //
     // This function does not filter out all invalid numbers. It depends in the context-sensitive
     // decoding of the text for that. For example, the number of allowed digits an an Ieee32` and
-    // an `Ieee64` constant are different.
+    // an `Ieee64` constant are different. It does, however, reject the specific malformed forms
+    // listed below at their point of occurrence, rather than deferring to a later, less precise
+    // error: a `0x` prefix with no hex digit following, an exponent marker with no digit
+    // following, a `NaN:`/`sNaN:` prefix with no hex payload following, and a lone sign not
+    // followed by a digit or a recognized `NaN`/`Inf` spelling.
     fn scan_number(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
         let begin = self.pos;
-        let loc = self.loc();
+        let start = self.loc();
         let mut is_float = false;
+        let mut is_nan_payload = false;
 
         // Skip a leading sign.
-        if self.lookahead == Some('-') {
+        let had_sign = self.lookahead == Some(b'-');
+        if had_sign {
+            self.next_ch();
+        }
+        if had_sign && !self.lookahead.map_or(false, |ch| ch.is_ascii_digit()) &&
+           !self.looking_at("NaN") && !self.looking_at("Inf") && !self.looking_at("sNaN") {
+            let end = self.loc();
+            return error(Error::InvalidNumberFormat, start, end);
+        }
+
+        // A hex literal requires at least one hex digit after the `0x` prefix.
+        let is_hex = self.looking_at("0x");
+        if is_hex &&
+           !self.source
+                .as_bytes()
+                .get(self.pos + 2)
+                .map_or(false, |b| b.is_ascii_hexdigit()) {
+            // Consume the `0x` prefix so the lexer always makes forward progress, even though
+            // the token it produced is malformed.
             self.next_ch();
+            self.next_ch();
+            let end = self.loc();
+            return error(Error::ExpectedHexadecimalDigit, start, end);
         }
 
         // Check for NaNs with payloads.
         if self.looking_at("NaN:") || self.looking_at("sNaN:") {
             // Skip the `NaN:` prefix, the loop below won't accept it.
             // We expect a hexadecimal number to follow the colon.
-            while self.next_ch() != Some(':') {}
+            while self.next_ch() != Some(b':') {}
             is_float = true;
+            is_nan_payload = true;
+            if !self.next_ch().map_or(false, |ch| ch.is_ascii_hexdigit()) {
+                let end = self.loc();
+                return error(Error::ExpectedNaNPayload, start, end);
+            }
         } else if self.looking_at("NaN") || self.looking_at("Inf") {
             // This is Inf or a default quiet NaN.
             is_float = true;
         }
 
         // Look for the end of this number. Detect the radix point if there is one.
+        let mut ch = self.next_ch();
         loop {
-            match self.next_ch() {
-                Some('-') | Some('_') => {}
-                Some('.') => is_float = true,
-                Some(ch) if ch.is_alphanumeric() => {}
+            match ch {
+                Some(b'-') | Some(b'_') => {}
+                Some(b'.') => is_float = true,
+                Some(b'p') | Some(b'P') if is_hex => {
+                    is_float = true;
+                    if !self.scan_exponent_digits() {
+                        let end = self.loc();
+                        return error(Error::ExpectedFloatExponent, start, end);
+                    }
+                    ch = self.lookahead;
+                    continue;
+                }
+                Some(b'e') | Some(b'E') if !is_hex && !is_nan_payload => {
+                    is_float = true;
+                    if !self.scan_exponent_digits() {
+                        let end = self.loc();
+                        return error(Error::ExpectedFloatExponent, start, end);
+                    }
+                    ch = self.lookahead;
+                    continue;
+                }
+                Some(ch) if ch.is_ascii_alphanumeric() => {}
                 _ => break,
             }
+            ch = self.next_ch();
         }
         let text = &self.source[begin..self.pos];
+        let end = self.loc();
         if is_float {
-            token(Token::Float(text), loc)
+            token(Token::Float(text), start, end)
         } else {
-            token(Token::Integer(text), loc)
+            token(Token::Integer(text), start, end)
+        }
+    }
+
+    // Having just matched an exponent marker (`p`/`P`/`e`/`E`) in `lookahead`, consume an
+    // optional sign followed by one or more decimal digits. Leaves `lookahead` positioned at the
+    // first character that isn't part of the exponent. Returns `false` if no digit was found.
+    fn scan_exponent_digits(&mut self) -> bool {
+        let mut ch = self.next_ch();
+        if ch == Some(b'-') || ch == Some(b'+') {
+            ch = self.next_ch();
+        }
+        let mut any_digits = false;
+        while let Some(c) = ch {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            any_digits = true;
+            ch = self.next_ch();
         }
+        any_digits
     }
 
     // Scan a 'word', which is an identifier-like sequence of characters beginning with '_' or an
-    // alphabetic char, followed by zero or more alphanumeric or '_' characters.
+    // ASCII letter, followed by zero or more alphanumeric or '_' characters. A non-ASCII byte is
+    // only decoded (and only ever accepted) once already inside a word, never as its first byte.
     fn scan_word(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
         let begin = self.pos;
-        let loc = self.loc();
+        let start = self.loc();
 
-        assert!(self.lookahead == Some('_') || self.lookahead.unwrap().is_alphabetic());
+        assert!(self.lookahead == Some(b'_') || self.lookahead.unwrap().is_ascii_alphabetic());
         loop {
-            match self.next_ch() {
-                Some('_') => {}
-                Some(ch) if ch.is_alphanumeric() => {}
+            match self.lookahead {
+                Some(b'_') => {
+                    self.next_ch();
+                }
+                Some(b) if b.is_ascii_alphanumeric() => {
+                    self.next_ch();
+                }
+                Some(b) if b >= 0x80 => {
+                    match self.source[self.pos..].chars().next() {
+                        Some(ch) if ch.is_alphanumeric() => self.advance_by_char(ch),
+                        _ => break,
+                    }
+                }
                 _ => break,
             }
         }
         let text = &self.source[begin..self.pos];
+        let end = self.loc();
 
         // Look for numbered well-known entities like ebb15, v45, ...
         token(split_entity_name(text)
@@ -275,7 +582,8 @@ impl<'a> Lexer<'a> {
                           .or_else(|| Self::value_type(text, prefix, number))
                   })
                   .unwrap_or(Token::Identifier(text)),
-              loc)
+              start,
+              end)
     }
 
     // If prefix is a well-known entity prefix and suffix is a valid entity number, return the
@@ -326,40 +634,65 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    // Scan a single-byte punctuation token, given the byte the dispatch table classified as
+    // `Punctuation`.
+    fn scan_punctuation(&mut self, byte: u8) -> Result<LocatedToken<'a>, LocatedError> {
+        let tok = match byte {
+            b'(' => Token::LPar,
+            b')' => Token::RPar,
+            b'{' => Token::LBrace,
+            b'}' => Token::RBrace,
+            b',' => Token::Comma,
+            b'.' => Token::Dot,
+            b':' => Token::Colon,
+            b'=' => Token::Equal,
+            _ => unreachable!("byte classified as Punctuation must be one of the above"),
+        };
+        self.scan_char(tok)
+    }
+
     /// Get the next token or a lexical error.
     ///
     /// Return None when the end of the source is encountered.
     pub fn next(&mut self) -> Option<Result<LocatedToken<'a>, LocatedError>> {
         loop {
-            let loc = self.loc();
-            return match self.lookahead {
-                None => None,
-                Some(';') => Some(self.scan_comment()),
-                Some('(') => Some(self.scan_char(Token::LPar)),
-                Some(')') => Some(self.scan_char(Token::RPar)),
-                Some('{') => Some(self.scan_char(Token::LBrace)),
-                Some('}') => Some(self.scan_char(Token::RBrace)),
-                Some(',') => Some(self.scan_char(Token::Comma)),
-                Some('.') => Some(self.scan_char(Token::Dot)),
-                Some(':') => Some(self.scan_char(Token::Colon)),
-                Some('=') => Some(self.scan_char(Token::Equal)),
-                Some('-') => {
+            let start = self.loc();
+            let byte = match self.lookahead {
+                None => return None,
+                Some(b) => b,
+            };
+            return match self.class_table[byte as usize] {
+                ByteClass::Whitespace => {
+                    self.next_ch();
+                    continue;
+                }
+                ByteClass::Comment => Some(self.scan_comment()),
+                ByteClass::Hash => {
+                    if self.looking_at("#|") {
+                        Some(self.scan_block_comment())
+                    } else {
+                        // A bare `#` not followed by `|` isn't a recognized token.
+                        self.next_ch();
+                        let end = self.loc();
+                        Some(error(Error::InvalidChar, start, end))
+                    }
+                }
+                ByteClass::Quote => Some(self.scan_string()),
+                ByteClass::Punctuation => Some(self.scan_punctuation(byte)),
+                ByteClass::Sign => {
                     if self.looking_at("->") {
                         Some(self.scan_chars(2, Token::Arrow))
                     } else {
                         Some(self.scan_number())
                     }
                 }
-                Some(ch) if ch.is_digit(10) => Some(self.scan_number()),
-                Some(ch) if ch.is_alphabetic() => Some(self.scan_word()),
-                Some(ch) if ch.is_whitespace() => {
+                ByteClass::Digit => Some(self.scan_number()),
+                ByteClass::IdentStart => Some(self.scan_word()),
+                ByteClass::Other => {
+                    // Skip invalid byte, return error.
                     self.next_ch();
-                    continue;
-                }
-                _ => {
-                    // Skip invalid char, return error.
-                    self.next_ch();
-                    Some(error(Error::InvalidChar, loc))
+                    let end = self.loc();
+                    Some(error(Error::InvalidChar, start, end))
                 }
             };
         }
@@ -370,6 +703,7 @@ impl<'a> Lexer<'a> {
 mod tests {
     use super::trailing_digits;
     use super::*;
+    use std::borrow::Cow;
     use cretonne::ir::types;
     use cretonne::ir::{Value, Ebb};
     use error::Location;
@@ -398,12 +732,27 @@ mod tests {
         assert_eq!(split_entity_name("inst01"), None);
     }
 
-    fn token<'a>(token: Token<'a>, line: usize) -> Option<Result<LocatedToken<'a>, LocatedError>> {
-        Some(super::token(token, Location { line_number: line }))
+    // Build a `Location` from a line number, column number, and byte offset.
+    fn loc(line: usize, column: usize, offset: usize) -> Location {
+        Location {
+            line_number: line,
+            column: column,
+            offset: offset,
+        }
+    }
+
+    fn token<'a>(token: Token<'a>,
+                 start: Location,
+                 end: Location)
+                 -> Option<Result<LocatedToken<'a>, LocatedError>> {
+        Some(super::token(token, start, end))
     }
 
-    fn error<'a>(error: Error, line: usize) -> Option<Result<LocatedToken<'a>, LocatedError>> {
-        Some(super::error(error, Location { line_number: line }))
+    fn error<'a>(error: Error,
+                 start: Location,
+                 end: Location)
+                 -> Option<Result<LocatedToken<'a>, LocatedError>> {
+        Some(super::error(error, start, end))
     }
 
     #[test]
@@ -420,70 +769,217 @@ mod tests {
     #[test]
     fn lex_comment() {
         let mut lex = Lexer::new("; hello");
-        assert_eq!(lex.next(), token(Token::Comment("; hello"), 1));
+        assert_eq!(lex.next(),
+                   token(Token::Comment("; hello"), loc(1, 1, 0), loc(1, 8, 7)));
         assert_eq!(lex.next(), None);
 
         lex = Lexer::new("\n  ;hello\n;foo");
-        assert_eq!(lex.next(), token(Token::Comment(";hello"), 2));
-        assert_eq!(lex.next(), token(Token::Comment(";foo"), 3));
+        assert_eq!(lex.next(),
+                   token(Token::Comment(";hello"), loc(2, 3, 3), loc(2, 9, 9)));
+        assert_eq!(lex.next(),
+                   token(Token::Comment(";foo"), loc(3, 1, 10), loc(3, 5, 14)));
         assert_eq!(lex.next(), None);
 
         // Scan a comment after an invalid char.
         let mut lex = Lexer::new("#; hello");
-        assert_eq!(lex.next(), error(Error::InvalidChar, 1));
-        assert_eq!(lex.next(), token(Token::Comment("; hello"), 1));
+        assert_eq!(lex.next(),
+                   error(Error::InvalidChar, loc(1, 1, 0), loc(1, 2, 1)));
+        assert_eq!(lex.next(),
+                   token(Token::Comment("; hello"), loc(1, 2, 1), loc(1, 9, 8)));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn lex_block_comment() {
+        let mut lex = Lexer::new("#| hello |#");
+        assert_eq!(lex.next(),
+                   token(Token::Comment("#| hello |#"), loc(1, 1, 0), loc(1, 12, 11)));
+        assert_eq!(lex.next(), None);
+
+        // Nesting: the inner `#|...|#` doesn't close the outer comment.
+        let mut lex = Lexer::new("#| outer #| inner |# still outer |#");
+        assert_eq!(lex.next(),
+                   token(Token::Comment("#| outer #| inner |# still outer |#"),
+                         loc(1, 1, 0),
+                         loc(1, 36, 35)));
+        assert_eq!(lex.next(), None);
+
+        // Embedded newlines advance the line number.
+        let mut lex = Lexer::new("#| line1\nline2 |#");
+        assert_eq!(lex.next(),
+                   token(Token::Comment("#| line1\nline2 |#"), loc(1, 1, 0), loc(2, 9, 17)));
+        assert_eq!(lex.next(), None);
+
+        // Non-ASCII content doesn't panic and advances by whole `char`s.
+        let mut lex = Lexer::new("#| é |#");
+        assert_eq!(lex.next(),
+                   token(Token::Comment("#| é |#"), loc(1, 1, 0), loc(1, 8, 8)));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn lex_block_comment_errors() {
+        let mut lex = Lexer::new("#| open");
+        assert_eq!(lex.next(),
+                   error(Error::UnterminatedBlockComment, loc(1, 1, 0), loc(1, 8, 7)));
+        assert_eq!(lex.next(), None);
+
+        // A bare `#` not followed by `|` still lexes as an invalid char, not a block comment.
+        let mut lex = Lexer::new("#x");
+        assert_eq!(lex.next(),
+                   error(Error::InvalidChar, loc(1, 1, 0), loc(1, 2, 1)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("x"), loc(1, 2, 1), loc(1, 3, 2)));
         assert_eq!(lex.next(), None);
     }
 
     #[test]
     fn lex_chars() {
         let mut lex = Lexer::new("(); hello\n = :{, }.");
-        assert_eq!(lex.next(), token(Token::LPar, 1));
-        assert_eq!(lex.next(), token(Token::RPar, 1));
-        assert_eq!(lex.next(), token(Token::Comment("; hello"), 1));
-        assert_eq!(lex.next(), token(Token::Equal, 2));
-        assert_eq!(lex.next(), token(Token::Colon, 2));
-        assert_eq!(lex.next(), token(Token::LBrace, 2));
-        assert_eq!(lex.next(), token(Token::Comma, 2));
-        assert_eq!(lex.next(), token(Token::RBrace, 2));
-        assert_eq!(lex.next(), token(Token::Dot, 2));
+        assert_eq!(lex.next(),
+                   token(Token::LPar, loc(1, 1, 0), loc(1, 2, 1)));
+        assert_eq!(lex.next(),
+                   token(Token::RPar, loc(1, 2, 1), loc(1, 3, 2)));
+        assert_eq!(lex.next(),
+                   token(Token::Comment("; hello"), loc(1, 3, 2), loc(1, 10, 9)));
+        assert_eq!(lex.next(),
+                   token(Token::Equal, loc(2, 2, 11), loc(2, 3, 12)));
+        assert_eq!(lex.next(),
+                   token(Token::Colon, loc(2, 4, 13), loc(2, 5, 14)));
+        assert_eq!(lex.next(),
+                   token(Token::LBrace, loc(2, 5, 14), loc(2, 6, 15)));
+        assert_eq!(lex.next(),
+                   token(Token::Comma, loc(2, 6, 15), loc(2, 7, 16)));
+        assert_eq!(lex.next(),
+                   token(Token::RBrace, loc(2, 8, 17), loc(2, 9, 18)));
+        assert_eq!(lex.next(),
+                   token(Token::Dot, loc(2, 9, 18), loc(2, 10, 19)));
         assert_eq!(lex.next(), None);
     }
 
     #[test]
     fn lex_numbers() {
         let mut lex = Lexer::new(" 0 2_000 -1,0xf -0x0 0.0 0x0.4p-34");
-        assert_eq!(lex.next(), token(Token::Integer("0"), 1));
-        assert_eq!(lex.next(), token(Token::Integer("2_000"), 1));
-        assert_eq!(lex.next(), token(Token::Integer("-1"), 1));
-        assert_eq!(lex.next(), token(Token::Comma, 1));
-        assert_eq!(lex.next(), token(Token::Integer("0xf"), 1));
-        assert_eq!(lex.next(), token(Token::Integer("-0x0"), 1));
-        assert_eq!(lex.next(), token(Token::Float("0.0"), 1));
-        assert_eq!(lex.next(), token(Token::Float("0x0.4p-34"), 1));
+        assert_eq!(lex.next(),
+                   token(Token::Integer("0"), loc(1, 2, 1), loc(1, 3, 2)));
+        assert_eq!(lex.next(),
+                   token(Token::Integer("2_000"), loc(1, 4, 3), loc(1, 9, 8)));
+        assert_eq!(lex.next(),
+                   token(Token::Integer("-1"), loc(1, 10, 9), loc(1, 12, 11)));
+        assert_eq!(lex.next(),
+                   token(Token::Comma, loc(1, 12, 11), loc(1, 13, 12)));
+        assert_eq!(lex.next(),
+                   token(Token::Integer("0xf"), loc(1, 13, 12), loc(1, 16, 15)));
+        assert_eq!(lex.next(),
+                   token(Token::Integer("-0x0"), loc(1, 17, 16), loc(1, 21, 20)));
+        assert_eq!(lex.next(),
+                   token(Token::Float("0.0"), loc(1, 22, 21), loc(1, 25, 24)));
+        assert_eq!(lex.next(),
+                   token(Token::Float("0x0.4p-34"), loc(1, 26, 25), loc(1, 35, 34)));
         assert_eq!(lex.next(), None);
     }
 
+    #[test]
+    fn lex_number_errors() {
+        let mut lex = Lexer::new("0x");
+        assert_eq!(lex.next(),
+                   error(Error::ExpectedHexadecimalDigit, loc(1, 1, 0), loc(1, 3, 2)));
+        assert_eq!(lex.next(), None);
+
+        let mut lex = Lexer::new("1.5e");
+        assert_eq!(lex.next(),
+                   error(Error::ExpectedFloatExponent, loc(1, 1, 0), loc(1, 5, 4)));
+        assert_eq!(lex.next(), None);
+
+        let mut lex = Lexer::new("-NaN:");
+        assert_eq!(lex.next(),
+                   error(Error::ExpectedNaNPayload, loc(1, 1, 0), loc(1, 6, 5)));
+        assert_eq!(lex.next(), None);
+
+        // A lone sign, not followed by a digit or a recognized `NaN`/`Inf` spelling, doesn't
+        // start a number at all.
+        let mut lex = Lexer::new("-x");
+        assert_eq!(lex.next(),
+                   error(Error::InvalidNumberFormat, loc(1, 1, 0), loc(1, 2, 1)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("x"), loc(1, 2, 1), loc(1, 3, 2)));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn lex_strings() {
+        let mut lex = Lexer::new(r#""hello""#);
+        assert_eq!(lex.next(),
+                   token(Token::String(Cow::Borrowed("hello")), loc(1, 1, 0), loc(1, 8, 7)));
+        assert_eq!(lex.next(), None);
+
+        // Escapes decode into an owned string.
+        let mut lex = Lexer::new(r#""a\tb""#);
+        assert_eq!(lex.next(),
+                   token(Token::String(Cow::Owned("a\tb".to_string())),
+                         loc(1, 1, 0),
+                         loc(1, 7, 6)));
+        assert_eq!(lex.next(), None);
+
+        // A `\xNN` escape decodes a hexadecimal byte value.
+        let mut lex = Lexer::new(r#""\x41""#);
+        assert_eq!(lex.next(),
+                   token(Token::String(Cow::Owned("A".to_string())), loc(1, 1, 0), loc(1, 7, 6)));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn lex_string_errors() {
+        let mut lex = Lexer::new("\"unterminated");
+        assert_eq!(lex.next(),
+                   error(Error::UnterminatedString, loc(1, 1, 0), loc(1, 14, 13)));
+        assert_eq!(lex.next(), None);
+
+        let mut lex = Lexer::new("\"a\u{1}b\"");
+        assert_eq!(lex.next(),
+                   error(Error::ControlCharInString, loc(1, 1, 0), loc(1, 3, 2)));
+
+        let mut lex = Lexer::new(r#""bad\qend""#);
+        assert_eq!(lex.next(),
+                   error(Error::InvalidStringEscape, loc(1, 1, 0), loc(1, 6, 5)));
+    }
+
     #[test]
     fn lex_identifiers() {
         let mut lex = Lexer::new("v0 v00 vx01 ebb1234567890 ebb5234567890 v1x vx1 vxvx4 \
                                   function0 function b1 i32x4 f32x5");
         assert_eq!(lex.next(),
-                   token(Token::Value(Value::direct_with_number(0).unwrap()), 1));
-        assert_eq!(lex.next(), token(Token::Identifier("v00"), 1));
-        assert_eq!(lex.next(), token(Token::Identifier("vx01"), 1));
-        assert_eq!(lex.next(),
-                   token(Token::Ebb(Ebb::with_number(1234567890).unwrap()), 1));
-        assert_eq!(lex.next(), token(Token::Identifier("ebb5234567890"), 1));
-        assert_eq!(lex.next(), token(Token::Identifier("v1x"), 1));
-        assert_eq!(lex.next(),
-                   token(Token::Value(Value::table_with_number(1).unwrap()), 1));
-        assert_eq!(lex.next(), token(Token::Identifier("vxvx4"), 1));
-        assert_eq!(lex.next(), token(Token::Identifier("function0"), 1));
-        assert_eq!(lex.next(), token(Token::Identifier("function"), 1));
-        assert_eq!(lex.next(), token(Token::Type(types::B1), 1));
-        assert_eq!(lex.next(), token(Token::Type(types::I32.by(4).unwrap()), 1));
-        assert_eq!(lex.next(), token(Token::Identifier("f32x5"), 1));
+                   token(Token::Value(Value::direct_with_number(0).unwrap()),
+                         loc(1, 1, 0),
+                         loc(1, 3, 2)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("v00"), loc(1, 4, 3), loc(1, 7, 6)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("vx01"), loc(1, 8, 7), loc(1, 12, 11)));
+        assert_eq!(lex.next(),
+                   token(Token::Ebb(Ebb::with_number(1234567890).unwrap()),
+                         loc(1, 13, 12),
+                         loc(1, 26, 25)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("ebb5234567890"), loc(1, 27, 26), loc(1, 40, 39)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("v1x"), loc(1, 41, 40), loc(1, 44, 43)));
+        assert_eq!(lex.next(),
+                   token(Token::Value(Value::table_with_number(1).unwrap()),
+                         loc(1, 45, 44),
+                         loc(1, 48, 47)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("vxvx4"), loc(1, 49, 48), loc(1, 54, 53)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("function0"), loc(1, 55, 54), loc(1, 64, 63)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("function"), loc(1, 65, 64), loc(1, 73, 72)));
+        assert_eq!(lex.next(),
+                   token(Token::Type(types::B1), loc(1, 74, 73), loc(1, 76, 75)));
+        assert_eq!(lex.next(),
+                   token(Token::Type(types::I32.by(4).unwrap()), loc(1, 77, 76), loc(1, 82, 81)));
+        assert_eq!(lex.next(),
+                   token(Token::Identifier("f32x5"), loc(1, 83, 82), loc(1, 88, 87)));
         assert_eq!(lex.next(), None);
     }
-}
\ No newline at end of file
+}